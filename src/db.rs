@@ -0,0 +1,134 @@
+use crate::tokens;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{Map, Value};
+
+/// The two shapes a compile_commands.json entry's compiler invocation can take,
+/// per the clang JSON Compilation Database spec: a single shell-quoted `command`
+/// string, or an already-tokenized `arguments` array.
+pub enum CompileUnitBody<'a> {
+    Command(&'a mut String),
+    Arguments(&'a mut Vec<Value>),
+}
+
+/// Borrow whichever of `command`/`arguments` is present on a compile unit object;
+/// `arguments` wins when both are present. See [`unit_file_name`] for the `file` field.
+pub fn unit_body<'a>(map: &'a mut Map<String, Value>) -> Result<CompileUnitBody<'a>> {
+    if let Some(value) = map.get("arguments") {
+        if !value.is_array() {
+            return Err(anyhow!(
+                "the following compile unit's arguments field was not an array: {:?}",
+                value
+            ));
+        }
+        let Some(Value::Array(arguments)) = map.get_mut("arguments") else {
+            unreachable!("just checked that arguments is an array");
+        };
+        return Ok(CompileUnitBody::Arguments(arguments));
+    }
+
+    match map.get("command") {
+        None => {
+            return Err(anyhow!(
+                "the following compile unit did not have a command or arguments field: {:?}",
+                map
+            ))
+        }
+        Some(value) if !value.is_string() => {
+            return Err(anyhow!(
+                "the following compile unit's command field was not a string: {:?}",
+                value
+            ))
+        }
+        Some(_) => {}
+    }
+    let Some(Value::String(command)) = map.get_mut("command") else {
+        unreachable!("just checked that command is a string");
+    };
+    Ok(CompileUnitBody::Command(command))
+}
+
+/// Copies the `file` field out of a compile unit (for use in error messages).
+pub fn unit_file_name(map: &Map<String, Value>) -> Result<String> {
+    let Value::String(name) = map
+        .get("file")
+        .with_context(|| format!("The following compile unit did not have a file field: {:?}", map))?
+        .clone()
+    else {
+        return Err(anyhow!(
+            "the following compile unit's file name was not a string: {:?}",
+            map
+        ));
+    };
+    Ok(name)
+}
+
+/// Tokenizes `command` the way a POSIX shell would, runs `edit` over the
+/// resulting argv, then re-quotes and writes the tokens back into `command`.
+pub fn edit_command_tokens(
+    command: &mut String,
+    edit: impl FnOnce(&mut Vec<String>),
+) -> Result<()> {
+    let mut tokens = shlex::split(command)
+        .with_context(|| format!("could not tokenize compile command as a shell command line: {}", command))?;
+    edit(&mut tokens);
+    *command = shlex::try_join(tokens.iter().map(String::as_str))
+        .with_context(|| "could not re-quote compile command tokens")?;
+    Ok(())
+}
+
+/// Runs `edit` over an `arguments` token array, converting to/from the
+/// `Vec<String>` that the shared [`tokens`] helpers operate on.
+pub fn edit_arguments_tokens(
+    arguments: &mut Vec<Value>,
+    edit: impl FnOnce(&mut Vec<String>),
+) -> Result<()> {
+    let mut token_strings: Vec<String> = arguments
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("the following compile unit's arguments entry was not a string: {:?}", v))
+        })
+        .collect::<Result<_>>()?;
+    edit(&mut token_strings);
+    *arguments = token_strings.into_iter().map(Value::String).collect();
+    Ok(())
+}
+
+/// Runs the given whole-token edit over whichever of `command`/`arguments`
+/// this compile unit body holds.
+pub fn edit_body_tokens(
+    body: CompileUnitBody<'_>,
+    edit: impl FnOnce(&mut Vec<String>),
+) -> Result<()> {
+    match body {
+        CompileUnitBody::Command(command) => edit_command_tokens(command, edit),
+        CompileUnitBody::Arguments(arguments) => edit_arguments_tokens(arguments, edit),
+    }
+}
+
+/// Adds `-I<dir>` to a compile unit's compiler invocation.
+pub fn add_include(body: CompileUnitBody<'_>, dir: &str) -> Result<()> {
+    edit_body_tokens(body, |toks| tokens::add_include(toks, dir))
+}
+
+/// Removes `-I<dir>` from a compile unit's compiler invocation.
+pub fn delete_include(body: CompileUnitBody<'_>, dir: &str) -> Result<()> {
+    edit_body_tokens(body, |toks| tokens::delete_include(toks, dir))
+}
+
+/// Adds `-<arg>` to a compile unit's compiler invocation.
+pub fn add_arg(body: CompileUnitBody<'_>, arg: &str) -> Result<()> {
+    edit_body_tokens(body, |toks| tokens::add_arg(toks, arg))
+}
+
+/// Removes `-<arg>` from a compile unit's compiler invocation.
+pub fn delete_arg(body: CompileUnitBody<'_>, arg: &str) -> Result<()> {
+    edit_body_tokens(body, |toks| tokens::delete_arg(toks, arg))
+}
+
+/// Replaces a compile unit's `-<key>...` flag with one carrying `value`,
+/// inserting it if not already present.
+pub fn replace_arg(body: CompileUnitBody<'_>, key: &str, value: &str) -> Result<()> {
+    edit_body_tokens(body, |toks| tokens::replace_arg(toks, key, value))
+}
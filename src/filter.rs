@@ -0,0 +1,50 @@
+//! Selecting which compile units an edit applies to, via `--filter`/`--filter-regex`
+//! glob/regex predicates matched against each entry's `file` field.
+
+use anyhow::{Context, Result};
+use glob::Pattern;
+use regex::Regex;
+
+/// A predicate over a compile unit's `file` field, built once from the
+/// `--filter`/`--filter-regex`/`--exclude` CLI options and then reused for
+/// every entry in the database.
+pub struct UnitFilter {
+    globs: Vec<Pattern>,
+    regexes: Vec<Regex>,
+    exclude: bool,
+}
+
+impl UnitFilter {
+    pub fn new(globs: &[String], regexes: &[String], exclude: bool) -> Result<UnitFilter> {
+        if exclude && globs.is_empty() && regexes.is_empty() {
+            return Err(anyhow::anyhow!(
+                "--exclude has no effect without at least one --filter or --filter-regex"
+            ));
+        }
+
+        let globs = globs
+            .iter()
+            .map(|g| Pattern::new(g).with_context(|| format!("invalid --filter glob: {}", g)))
+            .collect::<Result<Vec<_>>>()?;
+        let regexes = regexes
+            .iter()
+            .map(|r| Regex::new(r).with_context(|| format!("invalid --filter-regex: {}", r)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(UnitFilter {
+            globs,
+            regexes,
+            exclude,
+        })
+    }
+
+    /// Whether a compile unit with the given `file` field should be edited.
+    /// With no globs/regexes configured, everything matches (preserving the
+    /// tool's default of applying edits to the whole database). `--exclude`
+    /// inverts the result.
+    pub fn matches(&self, file: &str) -> bool {
+        let matched = self.globs.is_empty() && self.regexes.is_empty()
+            || self.globs.iter().any(|pattern| pattern.matches(file))
+            || self.regexes.iter().any(|regex| regex.is_match(file));
+        matched != self.exclude
+    }
+}
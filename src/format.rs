@@ -0,0 +1,82 @@
+//! Reading/writing compilation databases in any of the formats clangd-adjacent
+//! tooling produces them in, dispatching on file extension (or an explicit
+//! override) while keeping `serde_json::Value` as the in-memory model.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+
+/// The serialization formats a compilation database can be read from or
+/// written to.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Json5,
+    Yaml,
+    Msgpack,
+}
+
+impl Format {
+    /// Infers the format from a path's file extension.
+    pub fn from_extension(path: &str) -> Result<Format> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Ok(Format::Json),
+            "json5" => Ok(Format::Json5),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            "msgpack" | "mp" => Ok(Format::Msgpack),
+            _ => Err(anyhow::anyhow!(
+                "could not infer a format from the extension of {}; --input-format/--output-format override it for --compile-commands/--output",
+                path
+            )),
+        }
+    }
+}
+
+/// Reads a compilation database from `path` in the given format.
+pub fn read(path: &str, format: Format) -> Result<Value> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path))?;
+    let mut reader = BufReader::new(file);
+    match format {
+        Format::Json => serde_json::from_reader(reader)
+            .with_context(|| format!("Could not parse {} as json", path)),
+        Format::Json5 => {
+            let mut contents = String::new();
+            reader
+                .read_to_string(&mut contents)
+                .with_context(|| format!("Could not read {}", path))?;
+            json5::from_str(&contents).with_context(|| format!("Could not parse {} as json5", path))
+        }
+        Format::Yaml => serde_yaml::from_reader(reader)
+            .with_context(|| format!("Could not parse {} as yaml", path)),
+        Format::Msgpack => rmp_serde::from_read(reader)
+            .with_context(|| format!("Could not parse {} as msgpack", path)),
+    }
+}
+
+/// Writes a compilation database to `path` in the given format.
+pub fn write(path: &str, value: &Value, format: Format) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Could not open {}", path))?;
+    let mut writer = BufWriter::new(file);
+    match format {
+        Format::Json => serde_json::to_writer_pretty(writer, value)
+            .with_context(|| "Could not serialize modified compile commands as json"),
+        Format::Json5 => {
+            let contents = json5::to_string(value)
+                .with_context(|| "Could not serialize modified compile commands as json5")?;
+            std::io::Write::write_all(&mut writer, contents.as_bytes())
+                .with_context(|| format!("Could not write {}", path))
+        }
+        Format::Yaml => serde_yaml::to_writer(writer, value)
+            .with_context(|| "Could not serialize modified compile commands as yaml"),
+        Format::Msgpack => rmp_serde::encode::write(&mut writer, value)
+            .with_context(|| "Could not serialize modified compile commands as msgpack"),
+    }
+}
@@ -1,8 +1,24 @@
+mod db;
+mod filter;
+mod format;
+mod merge;
+mod tokens;
+
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use db::CompileUnitBody;
+use filter::UnitFilter;
+use format::Format;
+use merge::MergeStrategy;
 use serde_json::Value;
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+
+/// The two canonical shapes a compile unit's compiler invocation can be
+/// rewritten into via `--normalize-to`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum NormalizeTo {
+    Command,
+    Arguments,
+}
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -29,29 +45,93 @@ struct Args {
     /// removes the specified compile arguments from all compilation units in the database
     #[arg(long)]
     delete_arg: Vec<String>,
+
+    /// rewrites every compile unit's compiler invocation into the given canonical
+    /// form (`command` or `arguments`) before writing the output
+    #[arg(long, value_enum)]
+    normalize_to: Option<NormalizeTo>,
+
+    /// format of --compile-commands; inferred from its file extension if omitted
+    #[arg(short = 'r', long, value_enum)]
+    input_format: Option<Format>,
+
+    /// format of --output; inferred from its file extension if omitted
+    #[arg(short = 'w', long, value_enum)]
+    output_format: Option<Format>,
+
+    /// only edit compile units whose `file` matches this glob (repeatable; a
+    /// unit is edited if it matches any --filter or --filter-regex)
+    #[arg(long)]
+    filter: Vec<String>,
+
+    /// only edit compile units whose `file` matches this regex (repeatable)
+    #[arg(long)]
+    filter_regex: Vec<String>,
+
+    /// inverts --filter/--filter-regex, editing the compile units that do not match
+    #[arg(long)]
+    exclude: bool,
+
+    /// merges another compilation database into --compile-commands before
+    /// editing (repeatable); entries are keyed by (directory, file)
+    #[arg(long)]
+    merge: Vec<String>,
+
+    /// how to resolve duplicate (directory, file) entries across merged databases
+    #[arg(long, value_enum, default_value = "last")]
+    merge_strategy: MergeStrategy,
+
+    /// replaces the value of an existing `-<key>...` flag (matched by key, e.g.
+    /// `std`, `march`, `O`, `isystem`), or appends it if absent (repeatable);
+    /// e.g. `--replace-arg std=c++17`
+    #[arg(long, value_parser = parse_key_value_arg)]
+    replace_arg: Vec<(String, String)>,
+
+    /// convenience for `--replace-arg std=<value>`
+    #[arg(long)]
+    set_std: Option<String>,
+}
+
+/// Parses a `KEY=VALUE` argument for `--replace-arg`.
+fn parse_key_value_arg(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{}`", s))
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    // converting between formats (including compacting a database into a
+    // different one on the same extension) counts as a modification in its own
+    // right, so it isn't caught by the edit-flag check below
+    let converting_formats = args.input_format.is_some()
+        || args.output_format.is_some()
+        || args.compile_commands != args.output;
+
     if args.add_include.is_empty()
         && args.delete_include.is_empty()
         && args.add_arg.is_empty()
         && args.delete_arg.is_empty()
+        && args.normalize_to.is_none()
+        && args.merge.is_empty()
+        && args.replace_arg.is_empty()
+        && args.set_std.is_none()
+        && !converting_formats
     {
         println!("No modifications requested, exiting.");
         return Ok(());
     }
 
-    // load compile commands file into memory
-    let compile_commands_reader = BufReader::new(
-        File::open(&args.compile_commands)
-            .with_context(|| format!("Could not open {}", args.compile_commands))?,
-    );
+    // load the compile commands file into memory, inferring its format from the
+    // file extension unless the user overrode it
+    let input_format = match args.input_format {
+        Some(format) => format,
+        None => Format::from_extension(&args.compile_commands)?,
+    };
+    let compile_commands = format::read(&args.compile_commands, input_format)?;
 
-    // parse the json and ensure that the structure is always an array of objects
-    let compile_commands = serde_json::from_reader(compile_commands_reader)
-        .with_context(|| format!("Could not parse {} as json", args.compile_commands))?;
+    // ensure that the structure is always an array of objects
     let mut compile_commands: Vec<Value> = match compile_commands {
         Value::Array(arr) => arr,
         Value::Object(_) => vec![compile_commands],
@@ -63,6 +143,31 @@ fn main() -> Result<()> {
         }
     };
 
+    // merge in any additional compilation databases before the add/delete
+    // editing passes run, so the transformations apply to the unified set
+    if !args.merge.is_empty() {
+        let mut databases = vec![compile_commands];
+        for merge_path in &args.merge {
+            let merge_format = Format::from_extension(merge_path)?;
+            let merge_value = format::read(merge_path, merge_format)?;
+            let merge_database: Vec<Value> = match merge_value {
+                Value::Array(arr) => arr,
+                Value::Object(_) => vec![merge_value],
+                _ => {
+                    return Err(anyhow!(
+                        "{} file was not formatted correctly, the top level item must be an array or object",
+                        merge_path
+                    ))
+                }
+            };
+            databases.push(merge_database);
+        }
+        compile_commands = merge::merge_databases(databases, args.merge_strategy)?;
+    }
+
+    let unit_filter = UnitFilter::new(&args.filter, &args.filter_regex, args.exclude)?;
+    let mut matched_count = 0;
+
     for compile_unit in compile_commands.iter_mut() {
         // a compile command file consists of "compile units" each of which is a json object,
         // here we unpack that from the json structure
@@ -71,68 +176,120 @@ fn main() -> Result<()> {
         };
 
         // copy the filename out of the json object (for use in error messages)
-        let Value::String(name) = map
-            .get("file")
-            .with_context(|| {
-                format!(
-                    "The following compile unit did not have a command field: {:?}",
-                    map
-                )
-            })?
-            .clone()
-        else {
-            return Err(anyhow!(
-                "the following compile unit's file name was not a string: {:?}",
-                map
-            ));
-        };
+        let name = db::unit_file_name(map)?;
 
-        // get a mutable reference to the compile command used for this compile unit
-        let Value::String(compile_command) = map.get_mut("command").with_context(|| {
-            format!(
-                "The following compile unit did not have a command field: {}",
-                name
-            )
-        })?
-        else {
-            return Err(anyhow!(
-                "the following compile unit's command field was not a string: {}",
-                name
-            ));
-        };
+        // a predicate computed once per entry, before the add/delete loops run,
+        // so --filter/--filter-regex/--exclude select which units get edited
+        if !unit_filter.matches(&name) {
+            continue;
+        }
+        matched_count += 1;
 
-        // modify the compile command as specified by the command line arguments (e.g add & remove include dirs)
+        // modify the compile unit's compiler invocation as specified by the command
+        // line arguments (e.g add & remove include dirs), tokenizing/re-quoting a
+        // `command` string or editing an `arguments` array as appropriate
         for include in &args.add_include {
-            if let Some(index) = compile_command.find("-I") {
-                compile_command.insert_str(index, &format!(" -I{} ", include));
-            }
+            db::add_include(db::unit_body(map)?, include)
+                .with_context(|| format!("in compile unit for {}", name))?;
         }
-
         for include in &args.delete_include {
-            let target_str = format!(" -I{}", include);
-            if let Some(start_idx) = compile_command.find(&target_str) {
-                compile_command.replace_range(start_idx..start_idx + target_str.len(), "");
-            }
+            db::delete_include(db::unit_body(map)?, include)
+                .with_context(|| format!("in compile unit for {}", name))?;
         }
-
         for arg in &args.add_arg {
-            compile_command.push_str(&format!(" -{}", arg));
+            db::add_arg(db::unit_body(map)?, arg)
+                .with_context(|| format!("in compile unit for {}", name))?;
         }
-
         for arg in &args.delete_arg {
-            let target_str = format!(" -{}", arg);
-            if let Some(start_idx) = compile_command.find(&target_str) {
-                compile_command.replace_range(start_idx..start_idx + target_str.len(), "");
-            }
+            db::delete_arg(db::unit_body(map)?, arg)
+                .with_context(|| format!("in compile unit for {}", name))?;
+        }
+        for (key, value) in &args.replace_arg {
+            db::replace_arg(db::unit_body(map)?, key, value)
+                .with_context(|| format!("in compile unit for {}", name))?;
+        }
+        if let Some(std) = &args.set_std {
+            db::replace_arg(db::unit_body(map)?, "std", std)
+                .with_context(|| format!("in compile unit for {}", name))?;
         }
     }
 
-    // write modified compile commands back out
-    let compile_commands_writer = BufWriter::new(
-        File::create(&args.output).with_context(|| format!("Could not open {}", args.output))?,
-    );
-    serde_json::to_writer_pretty(compile_commands_writer, &Value::Array(compile_commands))
-        .with_context(|| "Could not serialize modified compile commands as JSON")?;
+    // only print a summary when the add/delete/replace loop above was actually
+    // asked to edit anything; a pure --normalize-to, --merge or format-conversion
+    // run still evaluates the filter predicate but applies no edits there, so
+    // reporting a count would be misleading
+    let editing_units = !args.add_include.is_empty()
+        || !args.delete_include.is_empty()
+        || !args.add_arg.is_empty()
+        || !args.delete_arg.is_empty()
+        || !args.replace_arg.is_empty()
+        || args.set_std.is_some();
+    if editing_units {
+        println!(
+            "Modified {} of {} compilation units.",
+            matched_count,
+            compile_commands.len()
+        );
+    }
+
+    if let Some(normalize_to) = args.normalize_to {
+        for compile_unit in compile_commands.iter_mut() {
+            let Value::Object(map) = compile_unit else {
+                continue;
+            };
+            normalize_unit(map, normalize_to)?;
+        }
+    }
+
+    // write modified compile commands back out, inferring the output format from
+    // the file extension unless the user overrode it
+    let output_format = match args.output_format {
+        Some(format) => format,
+        None => Format::from_extension(&args.output)?,
+    };
+    format::write(&args.output, &Value::Array(compile_commands), output_format)?;
+
+    Ok(())
+}
+
+/// Rewrites a compile unit's compiler invocation into the requested canonical
+/// form, converting between a shell-quoted `command` string and a tokenized
+/// `arguments` array using the same shlex tokenizer/quoter as the add/delete
+/// operations.
+fn normalize_unit(
+    map: &mut serde_json::Map<String, Value>,
+    normalize_to: NormalizeTo,
+) -> Result<()> {
+    let conversion = match (db::unit_body(map)?, normalize_to) {
+        (CompileUnitBody::Command(_), NormalizeTo::Command) => None,
+        (CompileUnitBody::Arguments(_), NormalizeTo::Arguments) => None,
+        (CompileUnitBody::Command(command), NormalizeTo::Arguments) => {
+            let tokens = shlex::split(command).with_context(|| {
+                format!("could not tokenize compile command as a shell command line: {}", command)
+            })?;
+            let arguments = tokens.into_iter().map(Value::String).collect();
+            Some(("arguments", Value::Array(arguments)))
+        }
+        (CompileUnitBody::Arguments(arguments), NormalizeTo::Command) => {
+            let tokens = arguments
+                .iter()
+                .map(|v| {
+                    v.as_str().ok_or_else(|| {
+                        anyhow!("the following compile unit's arguments entry was not a string: {:?}", v)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let command = shlex::try_join(tokens)
+                .with_context(|| "could not re-quote compile command tokens")?;
+            Some(("command", Value::String(command)))
+        }
+    };
+
+    if let Some((field, value)) = conversion {
+        map.remove("command");
+        map.remove("arguments");
+        map.insert(field.to_string(), value);
+    }
 
     Ok(())
 }
@@ -0,0 +1,72 @@
+//! Combining multiple compilation databases into one, keyed by
+//! `(directory, file)` so fragments emitted per-target (as cc-rs and similar
+//! tools do) can be unified before the add/delete editing passes run.
+
+use crate::db;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// How to resolve two compile units that key to the same `(directory, file)`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeStrategy {
+    First,
+    Last,
+    Error,
+}
+
+/// Merges `databases` in order, keeping insertion order of first occurrence
+/// and resolving duplicate `(directory, file)` keys according to `strategy`.
+pub fn merge_databases(
+    databases: Vec<Vec<Value>>,
+    strategy: MergeStrategy,
+) -> Result<Vec<Value>> {
+    let mut merged: Vec<Value> = Vec::new();
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+
+    for database in databases {
+        for unit in database {
+            let Value::Object(map) = &unit else {
+                return Err(anyhow!(
+                    "the following compile unit was not in the form of a JSON object: {}",
+                    unit
+                ));
+            };
+            let key = unit_key(map)?;
+
+            match index.get(&key) {
+                None => {
+                    index.insert(key, merged.len());
+                    merged.push(unit);
+                }
+                Some(&existing_index) => match strategy {
+                    MergeStrategy::Last => merged[existing_index] = unit,
+                    MergeStrategy::First => {}
+                    MergeStrategy::Error => {
+                        return Err(anyhow!(
+                            "duplicate compile unit for directory {:?}, file {:?}; pass --merge-strategy to resolve",
+                            key.0,
+                            key.1
+                        ))
+                    }
+                },
+            }
+        }
+    }
+
+    Ok(merged)
+}
+
+/// The `(directory, file)` key a compile unit is merged on. Tolerates units
+/// in either the `command` or `arguments` form since the key only depends on
+/// `directory`/`file`.
+fn unit_key(map: &Map<String, Value>) -> Result<(String, String)> {
+    let directory = map
+        .get("directory")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let file = db::unit_file_name(map)?;
+    Ok((directory, file))
+}
@@ -0,0 +1,214 @@
+//! Whole-token add/remove operations over a tokenized argv, shared by both the
+//! `command` (shlex-tokenized) and `arguments` (already-tokenized) compile unit
+//! forms so the two stay behaviorally identical.
+
+/// Returns true if `tokens[i]` is `-I<dir>`, or `tokens[i]` is `-I` and
+/// `tokens[i + 1]` is `<dir>` (clang accepts both forms for `-I`).
+fn is_include_token(tokens: &[String], i: usize, dir: &str) -> bool {
+    if tokens[i] == format!("-I{}", dir) {
+        return true;
+    }
+    tokens[i] == "-I" && tokens.get(i + 1).map(String::as_str) == Some(dir)
+}
+
+/// Adds `-I<dir>` to `tokens`, unless an equivalent `-I<dir>` or `-I <dir>`
+/// token (pair) is already present.
+pub fn add_include(tokens: &mut Vec<String>, dir: &str) {
+    let already_present = (0..tokens.len()).any(|i| is_include_token(tokens, i, dir));
+    if already_present {
+        return;
+    }
+    tokens.push(format!("-I{}", dir));
+}
+
+/// Removes any `-I<dir>` or `-I <dir>` occurrence from `tokens`.
+pub fn delete_include(tokens: &mut Vec<String>, dir: &str) {
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i] == format!("-I{}", dir) {
+            tokens.remove(i);
+        } else if tokens[i] == "-I" && tokens.get(i + 1).map(String::as_str) == Some(dir) {
+            tokens.remove(i + 1);
+            tokens.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Adds `-<arg>` to `tokens`, unless it is already present as a whole token.
+pub fn add_arg(tokens: &mut Vec<String>, arg: &str) {
+    let token = format!("-{}", arg);
+    if tokens.contains(&token) {
+        return;
+    }
+    tokens.push(token);
+}
+
+/// Removes `-<arg>` from `tokens` wherever it appears as a whole token.
+pub fn delete_arg(tokens: &mut Vec<String>, arg: &str) {
+    let token = format!("-{}", arg);
+    tokens.retain(|t| *t != token);
+}
+
+/// How a value-taking flag attaches its value to its key, e.g. `-std=c++17`
+/// vs `-O2` vs `-isystem /usr/include`.
+enum ArgStyle {
+    /// `-key=value`, e.g. `-std=`, `-march=`.
+    Equals,
+    /// `-keyvalue`, no separator, e.g. `-O2`.
+    Attached,
+    /// `-keyvalue` or `-key value` as a split pair, e.g. `-isystem`/`-I`.
+    SplitOrAttached,
+}
+
+fn arg_style(key: &str) -> ArgStyle {
+    match key {
+        "O" => ArgStyle::Attached,
+        "I" | "isystem" => ArgStyle::SplitOrAttached,
+        _ => ArgStyle::Equals,
+    }
+}
+
+/// Replaces `tokens`' existing `-<key>...` flag (in whichever form it was
+/// written) with one carrying `value`, or appends it if the key isn't
+/// present yet. This is the prefix-keyed counterpart to [`add_arg`]/
+/// [`delete_arg`] for flags that take a value, avoiding the
+/// duplicate-accumulation that repeated `add_arg` calls would cause.
+pub fn replace_arg(tokens: &mut Vec<String>, key: &str, value: &str) {
+    let style = arg_style(key);
+    let prefix_bare = format!("-{}", key);
+    let prefix_eq = format!("-{}=", key);
+
+    let mut i = 0;
+    while i < tokens.len() {
+        if matches!(style, ArgStyle::SplitOrAttached)
+            && tokens[i] == prefix_bare
+            && i + 1 < tokens.len()
+        {
+            tokens.remove(i + 1);
+            tokens.remove(i);
+        } else if tokens[i].starts_with(&prefix_eq)
+            || (matches!(style, ArgStyle::Attached | ArgStyle::SplitOrAttached)
+                && tokens[i].starts_with(&prefix_bare)
+                && tokens[i] != prefix_bare)
+        {
+            tokens.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    let new_token = match style {
+        ArgStyle::Equals => format!("-{}={}", key, value),
+        ArgStyle::Attached | ArgStyle::SplitOrAttached => format!("-{}{}", key, value),
+    };
+    tokens.push(new_token);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toks(tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn add_include_dedupes_attached_form() {
+        let mut tokens = toks(&["clang++", "-Ifoo", "a.cpp"]);
+        add_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "-Ifoo", "a.cpp"]));
+    }
+
+    #[test]
+    fn add_include_dedupes_split_form() {
+        let mut tokens = toks(&["clang++", "-I", "foo", "a.cpp"]);
+        add_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "-I", "foo", "a.cpp"]));
+    }
+
+    #[test]
+    fn add_include_appends_when_absent() {
+        let mut tokens = toks(&["clang++", "a.cpp"]);
+        add_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp", "-Ifoo"]));
+    }
+
+    #[test]
+    fn add_include_does_not_match_substring() {
+        let mut tokens = toks(&["clang++", "-Ifoobar", "a.cpp"]);
+        add_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "-Ifoobar", "a.cpp", "-Ifoo"]));
+    }
+
+    #[test]
+    fn delete_include_removes_attached_form() {
+        let mut tokens = toks(&["clang++", "-Ifoo", "a.cpp"]);
+        delete_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp"]));
+    }
+
+    #[test]
+    fn delete_include_removes_split_form() {
+        let mut tokens = toks(&["clang++", "-I", "foo", "a.cpp"]);
+        delete_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp"]));
+    }
+
+    #[test]
+    fn delete_include_ignores_substring_match() {
+        let mut tokens = toks(&["clang++", "-Ifoobar", "a.cpp"]);
+        delete_include(&mut tokens, "foo");
+        assert_eq!(tokens, toks(&["clang++", "-Ifoobar", "a.cpp"]));
+    }
+
+    #[test]
+    fn add_arg_dedupes_whole_token() {
+        let mut tokens = toks(&["clang++", "-O2", "a.cpp"]);
+        add_arg(&mut tokens, "O2");
+        assert_eq!(tokens, toks(&["clang++", "-O2", "a.cpp"]));
+    }
+
+    #[test]
+    fn delete_arg_does_not_touch_substring() {
+        let mut tokens = toks(&["clang++", "-O2", "a.cpp"]);
+        delete_arg(&mut tokens, "O");
+        assert_eq!(tokens, toks(&["clang++", "-O2", "a.cpp"]));
+    }
+
+    #[test]
+    fn replace_arg_rewrites_existing_equals_flag() {
+        let mut tokens = toks(&["clang++", "-std=c++17", "a.cpp"]);
+        replace_arg(&mut tokens, "std", "c++20");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp", "-std=c++20"]));
+    }
+
+    #[test]
+    fn replace_arg_inserts_equals_flag_when_absent() {
+        let mut tokens = toks(&["clang++", "a.cpp"]);
+        replace_arg(&mut tokens, "std", "c++20");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp", "-std=c++20"]));
+    }
+
+    #[test]
+    fn replace_arg_does_not_strip_unrelated_flag_sharing_key_prefix() {
+        let mut tokens = toks(&["clang++", "-stdlib=libc++", "-std=c++17", "a.cpp"]);
+        replace_arg(&mut tokens, "std", "c++20");
+        assert_eq!(tokens, toks(&["clang++", "-stdlib=libc++", "a.cpp", "-std=c++20"]));
+    }
+
+    #[test]
+    fn replace_arg_rewrites_attached_flag() {
+        let mut tokens = toks(&["clang++", "-O2", "a.cpp"]);
+        replace_arg(&mut tokens, "O", "3");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp", "-O3"]));
+    }
+
+    #[test]
+    fn replace_arg_rewrites_split_or_attached_flag() {
+        let mut tokens = toks(&["clang++", "-isystem", "/usr/include", "a.cpp"]);
+        replace_arg(&mut tokens, "isystem", "/opt/include");
+        assert_eq!(tokens, toks(&["clang++", "a.cpp", "-isystem/opt/include"]));
+    }
+}